@@ -1,25 +1,33 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::{collections::{HashSet, HashMap}, time::Duration, sync::Arc};
+use std::{collections::{HashMap, HashSet}, time::Duration, sync::Arc};
 use tokio::time;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, Mutex as AsyncMutex, Semaphore};
 use chrono::{TimeZone, Utc};
-use tracing::{info, error};
+use tracing::{info, error, warn};
+use futures::stream::{self, StreamExt};
 use rdkafka::{
     config::ClientConfig,
     producer::{FutureProducer, FutureRecord},
-    consumer::{Consumer, StreamConsumer},
+    consumer::{Consumer, ConsumerContext, BaseConsumer, StreamConsumer},
     message::{Message, BorrowedMessage},
+    admin::{AdminClient, AdminOptions, NewTopic, TopicReplication},
+    types::{RDKafkaErrorCode, RDKafkaRespErr},
+    topic_partition_list::TopicPartitionList,
+    client::ClientContext,
+    Offset,
 };
 use rdkafka::util::Timeout;
 use std::env;
 use axum::{
-    Router, 
+    Router,
     routing::get,
     http::StatusCode,
     Json,
     extract::State,
 };
+use metrics::{counter, gauge};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Story {
@@ -34,6 +42,8 @@ struct Story {
     by: Option<String>,     // author username
     text: Option<String>,   // self-post text content
     descendants: Option<i32>, // total comment count
+    #[serde(default)]
+    feed: String, // which polled feed this item came from (e.g. "topstories"); not part of the HN API response
 }
 
 // Domain count structure for API response
@@ -49,6 +59,7 @@ struct TopDomainsResponse {
     domains: Vec<DomainCount>,
     total_stories: usize,
     updated_at: String,
+    committed_offsets: HashMap<i32, i64>,
 }
 
 // Application state to be shared between components
@@ -58,6 +69,97 @@ struct AppState {
     top_domains: Arc<RwLock<Vec<DomainCount>>>,
     total_stories: Arc<RwLock<usize>>,
     last_updated: Arc<RwLock<String>>,
+    db: sled::Db,
+    // Per-partition offset of the last message folded into domain_counts,
+    // committed to Kafka periodically and persisted for restart recovery.
+    committed_offsets: Arc<RwLock<HashMap<i32, i64>>>,
+}
+
+const DOMAIN_KEY_PREFIX: &str = "domain:";
+const SEEN_KEY_PREFIX: &str = "seen:";
+const OFFSET_KEY_PREFIX: &str = "offset:";
+
+// Open (or create) the sled store used to durably back domain counts and
+// the set of already-processed story IDs.
+fn open_store() -> sled::Db {
+    let db_path = env::var("DB_PATH").unwrap_or_else(|_| "./data/hn_tracker.sled".to_string());
+    info!("Opening persistence store at {}", db_path);
+    sled::open(&db_path).expect("Failed to open sled store")
+}
+
+// Rehydrate domain counts from disk so a restart doesn't lose accumulated history.
+fn load_domain_counts(db: &sled::Db) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for entry in db.scan_prefix(DOMAIN_KEY_PREFIX) {
+        match entry {
+            Ok((key, value)) => {
+                if let Ok(key_str) = std::str::from_utf8(&key) {
+                    if let Some(domain) = key_str.strip_prefix(DOMAIN_KEY_PREFIX) {
+                        if let Ok(bytes) = <[u8; 8]>::try_from(value.as_ref()) {
+                            counts.insert(domain.to_string(), u64::from_le_bytes(bytes) as usize);
+                        }
+                    }
+                }
+            }
+            Err(e) => error!("Failed to read domain count entry from store: {}", e),
+        }
+    }
+    counts
+}
+
+// Check whether a story ID has already been processed, per the on-disk record.
+fn is_story_seen(db: &sled::Db, id: u32) -> bool {
+    match db.contains_key(format!("{}{}", SEEN_KEY_PREFIX, id)) {
+        Ok(seen) => seen,
+        Err(e) => {
+            error!("Failed to check seen state for story {}: {}", id, e);
+            false
+        }
+    }
+}
+
+// Mark a story ID as processed so it survives restarts.
+fn mark_story_seen(db: &sled::Db, id: u32) {
+    if let Err(e) = db.insert(format!("{}{}", SEEN_KEY_PREFIX, id), &[]) {
+        error!("Failed to persist seen marker for story {}: {}", id, e);
+    }
+}
+
+// Write an updated domain count through to the store.
+fn persist_domain_count(db: &sled::Db, domain: &str, count: usize) {
+    let key = format!("{}{}", DOMAIN_KEY_PREFIX, domain);
+    if let Err(e) = db.insert(key, &(count as u64).to_le_bytes()) {
+        error!("Failed to persist domain count for {}: {}", domain, e);
+    }
+}
+
+// Rehydrate the per-partition offset map so a restart can resume from the
+// last processed position instead of re-reading from `earliest`.
+fn load_committed_offsets(db: &sled::Db) -> HashMap<i32, i64> {
+    let mut offsets = HashMap::new();
+    for entry in db.scan_prefix(OFFSET_KEY_PREFIX) {
+        match entry {
+            Ok((key, value)) => {
+                if let Ok(key_str) = std::str::from_utf8(&key) {
+                    if let Some(partition_str) = key_str.strip_prefix(OFFSET_KEY_PREFIX) {
+                        if let (Ok(partition), Ok(bytes)) = (partition_str.parse::<i32>(), <[u8; 8]>::try_from(value.as_ref())) {
+                            offsets.insert(partition, i64::from_le_bytes(bytes));
+                        }
+                    }
+                }
+            }
+            Err(e) => error!("Failed to read offset entry from store: {}", e),
+        }
+    }
+    offsets
+}
+
+// Persist the last processed offset for a partition alongside the domain store.
+fn persist_committed_offset(db: &sled::Db, partition: i32, offset: i64) {
+    let key = format!("{}{}", OFFSET_KEY_PREFIX, partition);
+    if let Err(e) = db.insert(key, &offset.to_le_bytes()) {
+        error!("Failed to persist offset for partition {}: {}", partition, e);
+    }
 }
 
 fn extract_domain(url: &str) -> Option<String> {
@@ -66,40 +168,190 @@ fn extract_domain(url: &str) -> Option<String> {
         .map(|s| s.trim_start_matches("www.").to_string())
 }
 
-async fn fetch_latest_stories(client: &Client, count: usize) -> Result<Vec<Story>, reqwest::Error> {
-    // Fetch the IDs of the newest stories
-    let new_stories: Vec<u32> = client
-        .get("https://hacker-news.firebaseio.com/v0/newstories.json")
+// Caps the effective backoff delay so a flaky item can't stall the fetch loop for minutes.
+const MAX_BACKOFF_MS: u64 = 10_000;
+
+// Guards outbound requests to the HN API: paces requests to a configured
+// rate and caps how many can be in flight to the host at once, the same
+// way we'd want to treat any rate-limited upstream provider.
+struct RateLimiter {
+    tick_interval: AsyncMutex<time::Interval>,
+    inflight: Semaphore,
+}
+
+// Wraps the inflight semaphore permit so the hn_inflight_requests gauge
+// tracks the request for exactly as long as the permit is held.
+struct InflightPermit<'a> {
+    _permit: tokio::sync::SemaphorePermit<'a>,
+}
+
+impl<'a> Drop for InflightPermit<'a> {
+    fn drop(&mut self) {
+        gauge!("hn_inflight_requests").decrement(1.0);
+    }
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: f64, max_inflight: usize) -> Self {
+        let period = Duration::from_secs_f64(1.0 / requests_per_second.max(0.001));
+        let mut interval = time::interval(period);
+        // Default Burst behavior would let ticks missed during the 60s gap
+        // between poll cycles accumulate and fire back-to-back at the start
+        // of the next one, bursting past the configured rate.
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        RateLimiter {
+            tick_interval: AsyncMutex::new(interval),
+            inflight: Semaphore::new(max_inflight),
+        }
+    }
+
+    async fn acquire(&self) -> InflightPermit<'_> {
+        self.tick_interval.lock().await.tick().await;
+        let permit = self.inflight.acquire().await.expect("rate limiter semaphore closed");
+        gauge!("hn_inflight_requests").increment(1.0);
+        InflightPermit { _permit: permit }
+    }
+
+    fn from_env() -> Self {
+        let requests_per_second = env::var("HN_REQUESTS_PER_SECOND")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10.0);
+        let max_inflight = env::var("HN_MAX_INFLIGHT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(8);
+        info!("HN rate limiter configured: {} req/s, {} max inflight", requests_per_second, max_inflight);
+        RateLimiter::new(requests_per_second, max_inflight)
+    }
+}
+
+// Exponential backoff delay for a given attempt, capped at MAX_BACKOFF_MS.
+// The shift amount is clamped to 63 so a misconfigured HN_ITEM_MAX_RETRIES
+// (anything above 63) can't overflow-shift the u64 before the cap applies.
+fn backoff_delay_ms(base_delay_ms: u64, attempt: u32) -> u64 {
+    base_delay_ms.saturating_mul(1u64 << attempt.min(63)).min(MAX_BACKOFF_MS)
+}
+
+// Fetch a single item, retrying transient failures with exponential backoff
+// (base_delay_ms * 2^attempt, capped at MAX_BACKOFF_MS) before giving up.
+async fn fetch_item_with_retry(
+    client: &Client,
+    limiter: &RateLimiter,
+    id: u32,
+    feed: &str,
+    max_retries: u32,
+    base_delay_ms: u64,
+) -> Option<Story> {
+    let url = format!("https://hacker-news.firebaseio.com/v0/item/{}.json", id);
+
+    for attempt in 0..=max_retries {
+        {
+            let _permit = limiter.acquire().await;
+            match client.get(&url).send().await {
+                Ok(resp) => match resp.json::<Story>().await {
+                    Ok(mut story) => {
+                        counter!("hn_items_fetched_total").increment(1);
+                        story.feed = feed.to_string();
+                        return Some(story);
+                    }
+                    Err(e) => warn!("Failed to parse story {} (attempt {}): {}", id, attempt + 1, e),
+                },
+                Err(e) => warn!("Failed to fetch story {} (attempt {}): {}", id, attempt + 1, e),
+            }
+        }
+
+        if attempt < max_retries {
+            time::sleep(Duration::from_millis(backoff_delay_ms(base_delay_ms, attempt))).await;
+        }
+    }
+
+    error!("Giving up on story {} after {} attempts", id, max_retries + 1);
+    counter!("hn_fetch_failures_total").increment(1);
+    None
+}
+
+// Feed names accepted in HN_FEEDS, as they appear in the HN API path (<feed>.json).
+const KNOWN_FEEDS: &[&str] = &["newstories", "topstories", "beststories", "askstories", "showstories", "jobstories"];
+
+// Parse a comma-separated HN_FEEDS value into the list of known feeds to poll,
+// trimming whitespace, dropping empty entries, and warning on unrecognized names.
+fn parse_feed_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .filter(|feed| {
+            let known = KNOWN_FEEDS.contains(&feed.as_str());
+            if !known {
+                warn!("Ignoring unknown HN feed '{}'", feed);
+            }
+            known
+        })
+        .collect()
+}
+
+fn configured_feeds() -> Vec<String> {
+    parse_feed_list(&env::var("HN_FEEDS").unwrap_or_else(|_| "newstories".to_string()))
+}
+
+// Fetch the latest item IDs for a single feed (e.g. "topstories") and resolve
+// each one, tagging the result with the feed it came from.
+async fn fetch_latest_stories(client: &Client, count: usize, limiter: &RateLimiter, feed: &str) -> Result<Vec<Story>, reqwest::Error> {
+    // Fetch the IDs listed by this feed
+    let feed_url = format!("https://hacker-news.firebaseio.com/v0/{}.json", feed);
+    let item_ids: Vec<u32> = client
+        .get(&feed_url)
         .send()
         .await?
         .json()
         .await?;
-    
-    // Take only the most recent stories (they're already sorted, newest first)
-    let latest_ids: Vec<u32> = new_stories.into_iter().take(count).collect();
-    
-    // Fetch each story in parallel
-    let mut stories = Vec::new();
-    for id in latest_ids {
-        let url = format!("https://hacker-news.firebaseio.com/v0/item/{}.json", id);
-        match client.get(&url).send().await {
-            Ok(resp) => {
-                if let Ok(story) = resp.json::<Story>().await {
-                    stories.push(story);
-                }
-            }
-            Err(e) => {
-                error!("Failed to fetch story {}: {}", id, e);
-            }
-        }
-        
-        // Small delay to avoid rate limiting
-        time::sleep(Duration::from_millis(50)).await;
-    }
-    
+
+    // Take only the first `count` items, in the order the feed lists them
+    let latest_ids: Vec<u32> = item_ids.into_iter().take(count).collect();
+
+    let max_retries = env::var("HN_ITEM_MAX_RETRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3);
+    let base_delay_ms = env::var("HN_RETRY_BASE_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(200);
+    let concurrency = env::var("HN_FETCH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(8);
+
+    // Fetch items concurrently (bounded by HN_FETCH_CONCURRENCY, independent of
+    // the rate limiter's own inflight ceiling); order doesn't matter since
+    // downstream sorts by score/count anyway.
+    let stories: Vec<Story> = stream::iter(latest_ids)
+        .map(|id| fetch_item_with_retry(client, limiter, id, feed, max_retries, base_delay_ms))
+        .buffer_unordered(concurrency)
+        .filter_map(|story| async move { story })
+        .collect()
+        .await;
+
     Ok(stories)
 }
 
+// Drop items whose type isn't in HN_TYPE_FILTER, if that env var is set.
+// Unset means no filtering (all item types pass through, as before).
+fn passes_type_filter(story: &Story, allowlist: &Option<Vec<String>>) -> bool {
+    match allowlist {
+        None => true,
+        Some(allowed) => story.r#type.as_deref().map_or(false, |t| allowed.iter().any(|a| a == t)),
+    }
+}
+
+fn parse_type_allowlist(raw: &str) -> Vec<String> {
+    raw.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+fn configured_type_allowlist() -> Option<Vec<String>> {
+    env::var("HN_TYPE_FILTER").ok().map(|v| parse_type_allowlist(&v))
+}
+
 fn format_story(story: &Story) -> String {
     let timestamp = match Utc.timestamp_opt(story.time, 0).single() {
         Some(dt) => dt.to_rfc3339(),
@@ -111,7 +363,7 @@ fn format_story(story: &Story) -> String {
         .unwrap_or_else(|| "no domain".to_string());
     
     format!(
-        "ID: {}\nTitle: {}\nBy: {}\nTime: {}\nURL: {}\nDomain: {}\nScore: {}\nComments: {}\n",
+        "ID: {}\nTitle: {}\nBy: {}\nTime: {}\nURL: {}\nDomain: {}\nScore: {}\nComments: {}\nFeed: {}\n",
         story.id,
         story.title,
         story.by.as_deref().unwrap_or("anonymous"),
@@ -119,10 +371,51 @@ fn format_story(story: &Story) -> String {
         story.url.as_deref().unwrap_or("none"),
         domain,
         story.score.unwrap_or(0),
-        story.descendants.unwrap_or(0)
+        story.descendants.unwrap_or(0),
+        story.feed
     )
 }
 
+// Create the stories topic if it doesn't already exist, so the producer/consumer
+// pair is self-bootstrapping against a fresh cluster. Partition count and
+// replication factor are read from env so operators can scale domain-counting
+// consumers by fanning stories across partitions.
+async fn ensure_topic_exists(kafka_broker: &str, topic: &str) -> Result<(), String> {
+    let partitions: i32 = env::var("KAFKA_PARTITIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3);
+    let replication: i32 = env::var("KAFKA_REPLICATION")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1);
+
+    info!("Ensuring Kafka topic '{}' exists ({} partitions, replication {})", topic, partitions, replication);
+
+    let admin_client: AdminClient<_> = ClientConfig::new()
+        .set("bootstrap.servers", kafka_broker)
+        .create()
+        .map_err(|e| format!("Failed to create Kafka admin client: {}", e))?;
+
+    let new_topic = NewTopic::new(topic, partitions, TopicReplication::Fixed(replication));
+    let results = admin_client
+        .create_topics(&[new_topic], &AdminOptions::new())
+        .await
+        .map_err(|e| format!("Failed to create topic {}: {}", topic, e))?;
+
+    for result in results {
+        match result {
+            Ok(_) => info!("Topic '{}' is ready", topic),
+            Err((name, RDKafkaErrorCode::TopicAlreadyExists)) => {
+                info!("Topic '{}' already exists, continuing", name);
+            }
+            Err((name, code)) => return Err(format!("Failed to create topic {}: {:?}", name, code)),
+        }
+    }
+
+    Ok(())
+}
+
 async fn send_to_kafka(producer: &FutureProducer, topic: &str, story: &Story) -> Result<(), String> {
     // Serialize the story to JSON
     let story_json = match serde_json::to_string(story) {
@@ -144,6 +437,7 @@ async fn send_to_kafka(producer: &FutureProducer, topic: &str, story: &Story) ->
             let (partition, offset) = delivery;
             info!("Sent story ID {} to Kafka topic {} (partition: {}, offset: {})",
                 story.id, topic, partition, offset);
+            counter!("hn_stories_produced_total").increment(1);
             Ok(())
         }
         Err((e, _)) => {
@@ -162,7 +456,9 @@ async fn process_story_domain(story: &Story, app_state: &AppState) {
             let mut domain_counts = app_state.domain_counts.write().await;
             let count = domain_counts.entry(domain.clone()).or_insert(0);
             *count += 1;
-            
+            persist_domain_count(&app_state.db, &domain, *count);
+            gauge!("hn_distinct_domains").set(domain_counts.len() as f64);
+
             // Update total stories counter
             let mut total = app_state.total_stories.write().await;
             *total += 1;
@@ -204,6 +500,11 @@ async fn update_top_domains(app_state: &AppState) {
     info!("Updated top domains list");
 }
 
+// API handler exposing Prometheus-formatted metrics for operators
+async fn get_metrics(State(prometheus_handle): State<PrometheusHandle>) -> String {
+    prometheus_handle.render()
+}
+
 // API handler for retrieving top domains
 async fn get_top_domains(
     State(app_state): State<AppState>,
@@ -212,43 +513,113 @@ async fn get_top_domains(
     let top_domains = app_state.top_domains.read().await.clone();
     let total_stories = *app_state.total_stories.read().await;
     let last_updated = app_state.last_updated.read().await.clone();
-    
+    let committed_offsets = app_state.committed_offsets.read().await.clone();
+
     // Prepare response
     let response = TopDomainsResponse {
         domains: top_domains,
         total_stories,
         updated_at: last_updated,
+        committed_offsets,
     };
     
     Ok(Json(response))
 }
 
+// Consumer context that seeks newly-assigned partitions to their persisted
+// offset. Calling `consumer.assign()` once right after `subscribe()` is racy:
+// the actual group-join rebalance happens asynchronously and would silently
+// re-assign partitions per the broker's committed offsets afterwards. Hooking
+// the rebalance callback itself means our seek always wins.
+struct OffsetSeekContext {
+    persisted_offsets: HashMap<i32, i64>,
+}
+
+impl ClientContext for OffsetSeekContext {}
+
+impl ConsumerContext for OffsetSeekContext {
+    fn rebalance(
+        &self,
+        base_consumer: &BaseConsumer<Self>,
+        err: RDKafkaRespErr,
+        tpl: &mut TopicPartitionList,
+    ) {
+        match err {
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => {
+                for elem in tpl.elements_mut() {
+                    if let Some(&offset) = self.persisted_offsets.get(&elem.partition()) {
+                        if let Err(e) = elem.set_offset(Offset::Offset(offset + 1)) {
+                            error!("Failed to seek partition {} to persisted offset: {}", elem.partition(), e);
+                        }
+                    }
+                }
+                if let Err(e) = base_consumer.assign(tpl) {
+                    error!("Failed to assign rebalanced partitions: {}", e);
+                } else {
+                    info!("Assigned rebalanced partitions, seeking to persisted offsets where known: {:?}", tpl);
+                }
+            }
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS => {
+                if let Err(e) = base_consumer.unassign() {
+                    error!("Failed to unassign revoked partitions: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 // Create Kafka consumer that reads messages and updates domain counts
 async fn run_kafka_consumer(kafka_broker: String, topic: String, app_state: AppState) -> Result<(), String> {
-    // Create consumer
-    let consumer: StreamConsumer = ClientConfig::new()
-        .set("group.id", "hn-domain-counter")
-        .set("bootstrap.servers", &kafka_broker)
-        .set("enable.auto.commit", "true")
-        .set("auto.offset.reset", "earliest")
-        .create()
-        .expect("Failed to create Kafka consumer");
-    
-    // Subscribe to topic
+    // Create consumer with manual offset commits: an in-flight message folded
+    // into domain_counts is only "done" once we've explicitly committed past
+    // it, so a crash mid-batch re-delivers rather than silently losing it.
+    // The context seeks each partition to its persisted offset as soon as
+    // it's actually assigned, rather than racing the group-join rebalance.
+    let persisted_offsets = load_committed_offsets(&app_state.db);
+    let context = OffsetSeekContext { persisted_offsets: persisted_offsets.clone() };
+    // Wrapped in an Arc so fetch_watermarks can be offloaded to spawn_blocking
+    // (it's a blocking librdkafka FFI call) without borrowing across the await.
+    let consumer: Arc<StreamConsumer<OffsetSeekContext>> = Arc::new(
+        ClientConfig::new()
+            .set("group.id", "hn-domain-counter")
+            .set("bootstrap.servers", &kafka_broker)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create_with_context(context)
+            .expect("Failed to create Kafka consumer"),
+    );
+
+    // Subscribe to topic; partition assignment (and the seek to persisted
+    // offsets) happens in OffsetSeekContext::rebalance once the group join
+    // actually completes.
     consumer.subscribe(&[&topic])
         .expect("Failed to subscribe to topic");
-    
+
+    if !persisted_offsets.is_empty() {
+        info!("Will resume from persisted offsets once assigned: {:?}", persisted_offsets);
+    }
+    *app_state.committed_offsets.write().await = persisted_offsets;
+
     info!("Kafka consumer started, reading from topic: {}", topic);
-    
+
     // Process interval for updating top domains
     let mut update_interval = time::interval(Duration::from_secs(30));
-    
+    // Interval on which we explicitly commit the offsets we've processed so far
+    let mut commit_interval = time::interval(Duration::from_secs(5));
+
     // Main consumer loop
     loop {
         tokio::select! {
             _ = update_interval.tick() => {
                 update_top_domains(&app_state).await;
             }
+            _ = commit_interval.tick() => {
+                commit_offsets(&consumer, &topic, &app_state).await;
+                // Report lag in its own task so the loop goes straight back to recv()
+                let (lag_consumer, lag_topic, lag_state) = (consumer.clone(), topic.clone(), app_state.clone());
+                tokio::spawn(async move { report_consumer_lag(lag_consumer, lag_topic, &lag_state).await });
+            }
             message_result = consumer.recv() => {
                 match message_result {
                     Ok(message) => {
@@ -258,9 +629,18 @@ async fn run_kafka_consumer(kafka_broker: String, topic: String, app_state: AppS
                                 Ok(story) => {
                                     info!("Received story: {} (ID: {})", story.title, story.id);
                                     process_story_domain(&story, &app_state).await;
+                                    counter!("hn_consumer_messages_processed_total").increment(1);
+
+                                    // Record the offset locally; it's only committed (to Kafka
+                                    // and to the durable store) once folded into domain_counts.
+                                    let partition = message.partition();
+                                    let offset = message.offset();
+                                    app_state.committed_offsets.write().await.insert(partition, offset);
+                                    persist_committed_offset(&app_state.db, partition, offset);
                                 }
                                 Err(e) => {
                                     error!("Failed to parse message as Story: {}", e);
+                                    counter!("hn_consumer_parse_errors_total").increment(1);
                                 }
                             }
                         }
@@ -274,19 +654,77 @@ async fn run_kafka_consumer(kafka_broker: String, topic: String, app_state: AppS
             }
         }
     }
-    
+
     // This point should never be reached due to infinite loop
     #[allow(unreachable_code)]
     Ok(())
 }
 
+// Explicitly commit the offsets of messages already folded into domain_counts.
+async fn commit_offsets(consumer: &StreamConsumer<OffsetSeekContext>, topic: &str, app_state: &AppState) {
+    let offsets = app_state.committed_offsets.read().await.clone();
+    if offsets.is_empty() {
+        return;
+    }
+
+    let mut tpl = TopicPartitionList::new();
+    for (&partition, &offset) in offsets.iter() {
+        if let Err(e) = tpl.add_partition_offset(topic, partition, Offset::Offset(offset + 1)) {
+            error!("Failed to build commit offset for partition {}: {}", partition, e);
+            return;
+        }
+    }
+
+    if let Err(e) = consumer.commit(&tpl, rdkafka::consumer::CommitMode::Async) {
+        error!("Failed to commit offsets: {}", e);
+    }
+}
+
+// Estimate consumer lag per partition (high watermark minus our committed
+// offset) and publish it as a gauge for operators to monitor.
+async fn report_consumer_lag(consumer: Arc<StreamConsumer<OffsetSeekContext>>, topic: String, app_state: &AppState) {
+    let offsets = app_state.committed_offsets.read().await.clone();
+    if offsets.is_empty() {
+        return;
+    }
+
+    // fetch_watermarks is a blocking librdkafka FFI call bounded only by its own
+    // Timeout, not by the async scheduler; run it on the blocking pool so a
+    // slow/unreachable broker can't stall consumer.recv() for up to 5s per partition.
+    let lags = tokio::task::spawn_blocking(move || {
+        let mut lags = Vec::new();
+        for (partition, offset) in offsets {
+            match consumer.fetch_watermarks(&topic, partition, Timeout::After(Duration::from_secs(5))) {
+                Ok((_low, high)) => lags.push((partition, (high - offset - 1).max(0))),
+                Err(e) => error!("Failed to fetch watermarks for partition {}: {}", partition, e),
+            }
+        }
+        lags
+    })
+    .await
+    .unwrap_or_else(|e| {
+        error!("Consumer lag task panicked: {}", e);
+        Vec::new()
+    });
+
+    for (partition, lag) in lags {
+        gauge!("hn_consumer_lag", "partition" => partition.to_string()).set(lag as f64);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     // Initialize tracing for logging
     tracing_subscriber::fmt::init();
-    
+
     info!("HackerNews story tracker starting...");
-    
+
+    // Install the Prometheus recorder early so every gauge!/counter! call
+    // from here on (including rehydration below) actually records somewhere.
+    let prometheus_handle = PrometheusBuilder::new()
+        .install_recorder()
+        .expect("Failed to install Prometheus recorder");
+
     // Get Kafka broker from environment or use default
     let kafka_broker = env::var("KAFKA_BROKER").unwrap_or_else(|_| "localhost:9092".to_string());
     info!("Using Kafka broker: {}", kafka_broker);
@@ -300,14 +738,42 @@ async fn main() {
     
     // Topic to send stories to
     let topic = "hackernews-stories";
-    
+
+    // Make sure the topic exists before producing/consuming from it
+    if let Err(e) = ensure_topic_exists(&kafka_broker, topic).await {
+        error!("Failed to auto-provision Kafka topic: {}", e);
+    }
+
+    // Open the durable store and rehydrate accumulated domain counts from it
+    let db = open_store();
+    let domain_counts = load_domain_counts(&db);
+    let total_stories = domain_counts.values().sum();
+    info!("Rehydrated {} domains ({} total stories) from store", domain_counts.len(), total_stories);
+    gauge!("hn_distinct_domains").set(domain_counts.len() as f64);
+
     // Create application state
+    let committed_offsets = load_committed_offsets(&db);
     let app_state = AppState {
-        domain_counts: Arc::new(RwLock::new(HashMap::new())),
+        domain_counts: Arc::new(RwLock::new(domain_counts)),
         top_domains: Arc::new(RwLock::new(Vec::new())),
-        total_stories: Arc::new(RwLock::new(0)),
+        total_stories: Arc::new(RwLock::new(total_stories)),
         last_updated: Arc::new(RwLock::new(Utc::now().to_rfc3339())),
+        db: db.clone(),
+        committed_offsets: Arc::new(RwLock::new(committed_offsets)),
     };
+    update_top_domains(&app_state).await;
+
+    // Periodically flush the store so we bound fsync cost instead of syncing on every write
+    let flush_db = db.clone();
+    tokio::spawn(async move {
+        let mut flush_interval = time::interval(Duration::from_secs(10));
+        loop {
+            flush_interval.tick().await;
+            if let Err(e) = flush_db.flush_async().await {
+                error!("Failed to flush persistence store: {}", e);
+            }
+        }
+    });
     
     // Clone state and topic for consumer task
     let consumer_app_state = app_state.clone();
@@ -327,11 +793,16 @@ async fn main() {
         .allow_origin(tower_http::cors::Any)
         .allow_methods(tower_http::cors::Any)
         .allow_headers(tower_http::cors::Any);
-        
+
     // Create API router
     let app = Router::new()
         .route("/api/top-domains", get(get_top_domains))
         .with_state(app_state.clone())
+        .merge(
+            Router::new()
+                .route("/metrics", get(get_metrics))
+                .with_state(prometheus_handle),
+        )
         .layer(cors);
     
     // Start API server in a separate task
@@ -354,65 +825,130 @@ async fn main() {
         .build()
         .expect("Failed to create HTTP client");
     
-    // Track the IDs of stories we've already seen
-    let mut seen_story_ids = HashSet::new();
-    
+    // Rate limiter guarding outbound requests to the HN API
+    let hn_rate_limiter = RateLimiter::from_env();
+
+    // Feeds to poll and the item-type allowlist to apply before producing
+    let feeds = configured_feeds();
+    info!("Polling HN feeds: {:?}", feeds);
+    let type_allowlist = configured_type_allowlist();
+
     // Main loop - fetch stories every minute
     let mut interval = time::interval(Duration::from_secs(60));
     loop {
         interval.tick().await;
         info!("Fetching latest stories...");
-        
-        match fetch_latest_stories(&client, 100).await {
-            Ok(stories) => {
-                // Filter to only new stories
-                let new_stories: Vec<&Story> = stories.iter()
-                    .filter(|story| !seen_story_ids.contains(&story.id))
-                    .collect();
-                
-                if new_stories.is_empty() {
-                    info!("No new stories found");
-                } else {
-                    info!("Found {} new stories", new_stories.len());
-                    
-                    // Log each new story
-                    for story in &new_stories {
-                        // Add to seen IDs
-                        seen_story_ids.insert(story.id);
-                        
-                        // Format and log the story
-                        let story_text = format_story(story);
-                        info!("New story:\n{}", story_text);
-                        
-                        // Send to Kafka
-                        match send_to_kafka(&producer, topic, story).await {
-                            Ok(_) => info!("Successfully sent story ID {} to Kafka", story.id),
-                            Err(e) => error!("Failed to send story ID {} to Kafka: {}", story.id, e),
+
+        // Poll every configured feed and dedup by ID across them (the same
+        // item can surface in, say, both topstories and showstories)
+        let mut seen_ids_this_batch = HashSet::new();
+        let mut stories = Vec::new();
+        for feed in &feeds {
+            match fetch_latest_stories(&client, 100, &hn_rate_limiter, feed).await {
+                Ok(feed_stories) => {
+                    for story in feed_stories {
+                        if seen_ids_this_batch.insert(story.id) {
+                            stories.push(story);
                         }
-                        
-                        // Process the story domain locally as well (in case consumer is behind)
-                        process_story_domain(story, &app_state).await;
                     }
-                    
-                    // Update top domains after processing batch
-                    update_top_domains(&app_state).await;
-                }
-                
-                // Prevent the seen stories set from growing indefinitely
-                if seen_story_ids.len() > 1000 {
-                    // Keep only the most recent 500 story IDs
-                    let newest_ids: Vec<u32> = stories.iter()
-                        .map(|s| s.id)
-                        .collect();
-                    
-                    let old_count = seen_story_ids.len();
-                    seen_story_ids = newest_ids.into_iter().collect();
-                    info!("Cleaned story ID cache: {} -> {}", old_count, seen_story_ids.len());
                 }
+                Err(e) => error!("Failed to fetch {} feed: {}", feed, e),
             }
-            Err(e) => {
-                error!("Failed to fetch stories to kafka: {}", e);
+        }
+
+        // Filter to only new, allowed-type stories
+        let new_stories: Vec<&Story> = stories.iter()
+            .filter(|story| !is_story_seen(&app_state.db, story.id))
+            .filter(|story| passes_type_filter(story, &type_allowlist))
+            .collect();
+
+        if new_stories.is_empty() {
+            info!("No new stories found");
+        } else {
+            info!("Found {} new stories", new_stories.len());
+
+            // Log each new story
+            for story in &new_stories {
+                // Mark as seen in the durable store
+                mark_story_seen(&app_state.db, story.id);
+
+                // Format and log the story
+                let story_text = format_story(story);
+                info!("New story:\n{}", story_text);
+
+                // Send to Kafka; domain counting happens once, on the consumer
+                // side, when the message is actually delivered back to us. Folding
+                // it in here too would double-count every story by design.
+                match send_to_kafka(&producer, topic, story).await {
+                    Ok(_) => info!("Successfully sent story ID {} to Kafka", story.id),
+                    Err(e) => error!("Failed to send story ID {} to Kafka: {}", story.id, e),
+                }
             }
+
+            // Refresh the ranking in case the consumer has processed a batch since we last checked
+            update_top_domains(&app_state).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_delay_grows_exponentially_then_caps() {
+        assert_eq!(backoff_delay_ms(200, 0), 200);
+        assert_eq!(backoff_delay_ms(200, 1), 400);
+        assert_eq!(backoff_delay_ms(200, 2), 800);
+        assert_eq!(backoff_delay_ms(200, 10), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn backoff_delay_clamps_shift_for_oversized_attempt() {
+        // A misconfigured HN_ITEM_MAX_RETRIES far above 63 must not overflow-shift.
+        assert_eq!(backoff_delay_ms(200, 1000), MAX_BACKOFF_MS);
+        assert_eq!(backoff_delay_ms(200, u32::MAX), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn parse_feed_list_trims_and_drops_empty_and_unknown_feeds() {
+        assert_eq!(parse_feed_list("newstories"), vec!["newstories".to_string()]);
+        assert_eq!(
+            parse_feed_list(" topstories , showstories ,, bogusfeed"),
+            vec!["topstories".to_string(), "showstories".to_string()]
+        );
+        assert!(parse_feed_list("").is_empty());
+        assert!(parse_feed_list("not-a-real-feed").is_empty());
+    }
+
+    fn story_with_type(item_type: Option<&str>) -> Story {
+        Story {
+            id: 1,
+            title: "title".to_string(),
+            score: None,
+            time: 0,
+            kids: Vec::new(),
+            url: None,
+            r#type: item_type.map(|t| t.to_string()),
+            by: None,
+            text: None,
+            descendants: None,
+            feed: "newstories".to_string(),
         }
     }
+
+    #[test]
+    fn passes_type_filter_allows_everything_when_unset() {
+        assert!(passes_type_filter(&story_with_type(Some("comment")), &None));
+        assert!(passes_type_filter(&story_with_type(None), &None));
+    }
+
+    #[test]
+    fn passes_type_filter_enforces_allowlist() {
+        let allowlist = Some(parse_type_allowlist("story, ask"));
+        assert!(passes_type_filter(&story_with_type(Some("story")), &allowlist));
+        assert!(!passes_type_filter(&story_with_type(Some("comment")), &allowlist));
+        // No type reported at all can't match any configured allowlist entry.
+        assert!(!passes_type_filter(&story_with_type(None), &allowlist));
+    }
 }
\ No newline at end of file